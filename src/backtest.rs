@@ -1,5 +1,5 @@
 use crate::evaluation::{Evaluation, Metrics};
-use crate::models::WinRate;
+use crate::models::{SizingMode, TradeRecord, WinRate};
 use crate::utils::{cumulative_returns, normalise_returns, round_float};
 use ndarray::arr1;
 
@@ -8,26 +8,132 @@ pub struct Backtest {
     signals: Vec<f64>,
     trading_costs: f64,
     weight_asset_1: f64, // Capital percentage on asset 1 between 0 and 1.0
-    weight_asset_2: f64, // Capital percentage on asset 2 between 0 and 1.0
+    weight_asset_2: f64, // Capital percentage on asset 2 between 0 and 1.0, used when hedge_ratio is None
+    stop_loss: Option<f64>, // Fractional running-PnL loss that forces a flat exit
+    take_profit: Option<f64>, // Fractional running-PnL gain that forces a flat exit
+    hedge_ratio: Option<Vec<f64>>, // Per-bar beta from rolling_hedge_ratio, replaces weight_asset_2 when set
+    periods_per_year: f64, // e.g. 252 for daily, 52 for weekly, 12 for monthly returns
+    risk_free_rate: f64, // annual risk-free rate used to compute Sharpe/Sortino excess returns
+    sizing_mode: SizingMode, // leverage applied on top of weight_asset_1/weight_asset_2
+    max_leverage: f64, // ceiling applied to the leverage produced by sizing_mode
 }
 
 impl Backtest {
-    pub fn new(signals: Vec<f64>, trading_costs: f64, weight_asset_1: f64, weight_asset_2: f64) -> Self {
-        Self { weight_asset_1, trading_costs, weight_asset_2, signals }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        signals: Vec<f64>,
+        trading_costs: f64,
+        weight_asset_1: f64,
+        weight_asset_2: f64,
+        stop_loss: Option<f64>,
+        take_profit: Option<f64>,
+        hedge_ratio: Option<Vec<f64>>,
+        periods_per_year: f64,
+        risk_free_rate: f64,
+        sizing_mode: SizingMode,
+        max_leverage: f64
+    ) -> Self {
+        Self {
+            weight_asset_1, trading_costs, weight_asset_2, signals, stop_loss, take_profit,
+            hedge_ratio, periods_per_year, risk_free_rate, sizing_mode, max_leverage
+        }
     }
 
-    /// Trade Costs
-    /// Returns trading costs in correct sequence based on signals
-    fn trade_costs(&self) -> Vec<f64> {
-        let mut trading_costs: Vec<f64> = vec![0.0; self.signals.len()];
-        for i in 1..self.signals.len() {
+    /// Leverage Series
+    /// Per-bar leverage multiplier fed into the leg weights. `Fixed` applies a constant
+    /// leverage; `VolTarget` scales trailing realized volatility of the combined strategy
+    /// log-returns to annual terms and sizes leverage to hit `annual_vol`, defaulting to 1.0
+    /// until the window fills. Both are capped at `max_leverage`.
+    fn leverage_series(&self, strat_log_rets: &Vec<f64>) -> Vec<f64> {
+        match &self.sizing_mode {
+            SizingMode::Fixed(leverage) => vec![leverage.min(self.max_leverage); strat_log_rets.len()],
+            SizingMode::VolTarget { annual_vol, window } => {
+                let n: usize = strat_log_rets.len();
+                let mut leverage: Vec<f64> = vec![1.0_f64.min(self.max_leverage); n];
+
+                for i in *window..n {
+                    let trailing: &[f64] = &strat_log_rets[i - window..i];
+                    let mean: f64 = trailing.iter().sum::<f64>() / *window as f64;
+                    let variance: f64 = trailing.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / *window as f64;
+                    let realized_annual_vol: f64 = variance.sqrt() * self.periods_per_year.sqrt();
+
+                    leverage[i] = if realized_annual_vol == 0.0 {
+                        1.0_f64.min(self.max_leverage)
+                    } else {
+                        (annual_vol / realized_annual_vol).min(self.max_leverage)
+                    };
+                }
+
+                leverage
+            }
+        }
+    }
+
+    /// Asset 2 Weights
+    /// Per-bar capital weighting for asset 2: the rolling hedge ratio when supplied,
+    /// otherwise the fixed weight_asset_2 repeated across every bar
+    fn weights_asset_2(&self) -> Vec<f64> {
+        match &self.hedge_ratio {
+            Some(beta) => beta.clone(),
+            None => vec![self.weight_asset_2; self.signals.len()]
+        }
+    }
+
+    /// Apply Exit Policy
+    /// Overrides `signals` with forced flat exits once the running log-PnL of an open
+    /// position crosses `-stop_loss` or `+take_profit`. The position then stays flat until
+    /// the original signal sequence produces a fresh entry (or side switch).
+    fn apply_exit_policy(&self, log_rets: &Vec<f64>) -> Vec<f64> {
+        if self.stop_loss.is_none() && self.take_profit.is_none() {
+            return self.signals.clone();
+        }
+
+        let mut adjusted: Vec<f64> = self.signals.clone();
+        let mut running_pnl: f64 = 0.0;
+        let mut flat_from_next: bool = false;
+
+        for i in 1..adjusted.len() {
             let val: f64 = self.signals[i];
             let prev_val: f64 = self.signals[i - 1];
 
+            // Fresh entry or side switch resets the running position PnL
+            if val != 0.0 && (prev_val == 0.0 || val != prev_val) {
+                running_pnl = 0.0;
+                flat_from_next = false;
+            }
+
+            if flat_from_next {
+                adjusted[i] = 0.0;
+                continue;
+            }
+
+            if val != 0.0 {
+                running_pnl += log_rets[i];
+                let hit_stop_loss: bool = self.stop_loss.is_some_and(|sl| running_pnl <= -sl);
+                let hit_take_profit: bool = self.take_profit.is_some_and(|tp| running_pnl >= tp);
+                if hit_stop_loss || hit_take_profit {
+                    flat_from_next = true;
+                }
+            } else {
+                running_pnl = 0.0;
+            }
+        }
+
+        adjusted
+    }
+
+    /// Trade Costs
+    /// Returns trading costs in correct sequence based on signals
+    fn trade_costs(&self, signals: &Vec<f64>) -> Vec<f64> {
+        let mut trading_costs: Vec<f64> = vec![0.0; signals.len()];
+        for i in 1..signals.len() {
+            let val: f64 = signals[i];
+            let prev_val: f64 = signals[i - 1];
+
             // Trade Closed
             if val == 0.0 && prev_val != 0.0 {
                 trading_costs[i - 1] = -self.trading_costs;
-            
+
             // Trade Opened
             } else if val != 0.0 && prev_val == 0.0 {
                 trading_costs[i] = -self.trading_costs;
@@ -42,17 +148,23 @@ impl Backtest {
     }
 
     /// Win Rate Stats
-    /// Provide stats and win rates
-    fn win_rate_stats(&self, log_rets: &Vec<f64>) -> WinRate {
+    /// Provide stats and win rates, alongside a per-trade ledger (TradeRecord) built from
+    /// the same signal-transition scan. A position still open at the last bar is flushed as
+    /// a final TradeRecord with `closed: false` rather than dropped from the ledger.
+    pub(crate) fn win_rate_stats(&self, signals: &Vec<f64>, log_rets: &Vec<f64>, cum_rets: &Vec<f64>) -> (WinRate, Vec<TradeRecord>) {
         let mut opened: u32 = 0;
         let mut closed: u32 = 0;
         let mut closed_profit: u32 = 0;
         let mut curr_profit: f64 = 0.0;
         let mut is_open: bool = false;
 
-        for i in 1..self.signals.len() {
-            let val: f64 = self.signals[i];
-            let prev_val: f64 = self.signals[i - 1];
+        let mut trade_records: Vec<TradeRecord> = vec![];
+        let mut index_in: usize = 0;
+        let mut direction: f64 = 0.0;
+
+        for i in 1..signals.len() {
+            let val: f64 = signals[i];
+            let prev_val: f64 = signals[i - 1];
 
             // Trade Closed
             if val == 0.0 && prev_val != 0.0 {
@@ -61,12 +173,24 @@ impl Backtest {
                 if curr_profit > 0.0 {
                     closed_profit += 1;
                 }
+                trade_records.push(TradeRecord {
+                    index_in,
+                    index_out: i - 1,
+                    direction,
+                    cum_return_in: cum_rets[index_in],
+                    cum_return_out: cum_rets[i - 1],
+                    log_pnl: round_float(curr_profit, 4),
+                    bars_held: (i - 1) - index_in,
+                    closed: true
+                });
                 curr_profit = 0.0;
 
             // Trade Opened
             } else if val != 0.0 && prev_val == 0.0 {
                 is_open = true;
                 opened += 1;
+                index_in = i;
+                direction = val;
                 curr_profit += log_rets[i];
 
             // Trade Closed and Opened (switched sides)
@@ -75,22 +199,51 @@ impl Backtest {
                 if curr_profit > 0.0 {
                     closed_profit += 1;
                 }
-                curr_profit += log_rets[i];
+                trade_records.push(TradeRecord {
+                    index_in,
+                    index_out: i - 1,
+                    direction,
+                    cum_return_in: cum_rets[index_in],
+                    cum_return_out: cum_rets[i - 1],
+                    log_pnl: round_float(curr_profit, 4),
+                    bars_held: (i - 1) - index_in,
+                    closed: true
+                });
+                curr_profit = log_rets[i];
                 is_open = true;
                 opened += 1;
-            
+                index_in = i;
+                direction = val;
+
             // Accumulate profits
             } else if is_open {
                 curr_profit += log_rets[i];
             }
         }
-        
+
+        // Position still open at the end of the series: flush it as an unrealized trade row
+        // rather than silently dropping it from the ledger
+        if is_open {
+            let last: usize = signals.len() - 1;
+            trade_records.push(TradeRecord {
+                index_in,
+                index_out: last,
+                direction,
+                cum_return_in: cum_rets[index_in],
+                cum_return_out: cum_rets[last],
+                log_pnl: round_float(curr_profit, 4),
+                bars_held: last - index_in,
+                closed: false
+            });
+        }
+
         let mut win_rate: f64 = 0.0;
         if closed_profit > 0 && closed > 0 {
             win_rate = closed_profit as f64 / closed as f64;
         }
 
-        WinRate { win_rate: round_float(win_rate, 2), opened, closed, closed_profit }
+        let win_rate_stats = WinRate { win_rate: round_float(win_rate, 2), opened, closed, closed_profit };
+        (win_rate_stats, trade_records)
     }
 
     /// Add Vectors
@@ -102,19 +255,30 @@ impl Backtest {
         net_arr.to_vec()
     }
 
+    /// Multiply Vectors
+    /// Multiplies two vectors together, element-wise
+    fn mul_vecs(&self, vec_1: &Vec<f64>, vec_2: &Vec<f64>) -> Vec<f64> {
+        let arr_1 = arr1(&vec_1);
+        let arr_2 = arr1(&vec_2);
+        let net_arr = arr_1 * arr_2;
+        net_arr.to_vec()
+    }
+
     /// Construct Portfolio Returns
     /// Takes in log returns and computes portfolio returns as such:
     /// Asset_1: log_returns * signal (long, short neutral) * (sign as +1.0) * capital_weighting
     /// Asset_2: log_returns * signal (long, short neutral) * inverse (sign as -1.0) * capital_weighting
-    /// The inverse is used for asset_2 as the original signal was constructed for asset 1. Asset 2 is just the other side
-    fn construct_portfolio_returns(&self, log_rets: Vec<f64>, trading_costs: &Vec<f64>, sign: f64,  weight: f64) -> Vec<f64> {
+    /// The inverse is used for asset_2 as the original signal was constructed for asset 1. Asset 2 is just the other side.
+    /// `weights` is a per-bar capital weighting, allowing a constant weight or a rolling hedge ratio
+    fn construct_portfolio_returns(&self, signals: &Vec<f64>, log_rets: Vec<f64>, trading_costs: &Vec<f64>, sign: f64, weights: &Vec<f64>) -> Vec<f64> {
 
         // Get strategy returns
         let rets_arr = arr1(&log_rets);
-        let sig_arr = arr1(&self.signals);
-        let strat_log_rets_arr = rets_arr * sig_arr * sign * weight;
+        let sig_arr = arr1(signals);
+        let weight_arr = arr1(weights);
+        let strat_log_rets_arr = rets_arr * sig_arr * sign * weight_arr;
         let strat_log_rets = strat_log_rets_arr.to_vec();
-        
+
         // Add trading costs
         let strat_log_rets_with_costs: Vec<f64> = self.add_vecs(&strat_log_rets, trading_costs);
 
@@ -126,16 +290,54 @@ impl Backtest {
     /// Performs all steps needed to execute a full backtest for a pairs trade
     pub fn run_backtest(&self, log_rets_1: Vec<f64>, log_rets_2_opt: Option<Vec<f64>>) -> Result<Metrics, String> {
 
-        // Trading costs
-        let trading_costs: Vec<f64> = self.trade_costs();
+        // Per-bar capital weighting for asset 2 (rolling hedge ratio, or the fixed weight)
+        let weights_1: Vec<f64> = vec![self.weight_asset_1; self.signals.len()];
+        let weights_2: Vec<f64> = self.weights_asset_2();
+
+        // Preview strategy returns (no costs) under the raw signals, used to evaluate the
+        // stop-loss / take-profit exit policy before trading costs are applied
+        let zero_costs: Vec<f64> = vec![0.0; self.signals.len()];
+        let preview_log_rets_1: Vec<f64> = self.construct_portfolio_returns(&self.signals, log_rets_1.clone(), &zero_costs, 1.0, &weights_1);
+        let preview_log_returns: Vec<f64> = match &log_rets_2_opt {
+            Some(log_rets_2) => {
+                let preview_log_rets_2: Vec<f64> = self.construct_portfolio_returns(&self.signals, log_rets_2.clone(), &zero_costs, -1.0, &weights_2);
+                self.add_vecs(&preview_log_rets_1, &preview_log_rets_2)
+            },
+            None => preview_log_rets_1
+        };
+
+        // Signals after forcing flat exits on stop-loss / take-profit triggers
+        let signals: Vec<f64> = self.apply_exit_policy(&preview_log_returns);
+
+        // Trading costs under the adjusted signal path
+        let trading_costs: Vec<f64> = self.trade_costs(&signals);
+
+        // Unleveraged combined returns under the adjusted signals, used to size position leverage.
+        // Only needed for VolTarget (Fixed leverage doesn't look at the returns at all)
+        let leverage: Vec<f64> = match &self.sizing_mode {
+            SizingMode::Fixed(_) => self.leverage_series(&vec![0.0; signals.len()]),
+            SizingMode::VolTarget { .. } => {
+                let sized_preview_1: Vec<f64> = self.construct_portfolio_returns(&signals, log_rets_1.clone(), &zero_costs, 1.0, &weights_1);
+                let sized_preview_returns: Vec<f64> = match &log_rets_2_opt {
+                    Some(log_rets_2) => {
+                        let sized_preview_2: Vec<f64> = self.construct_portfolio_returns(&signals, log_rets_2.clone(), &zero_costs, -1.0, &weights_2);
+                        self.add_vecs(&sized_preview_1, &sized_preview_2)
+                    },
+                    None => sized_preview_1
+                };
+                self.leverage_series(&sized_preview_returns)
+            }
+        };
+        let weights_1: Vec<f64> = self.mul_vecs(&weights_1, &leverage);
+        let weights_2: Vec<f64> = self.mul_vecs(&weights_2, &leverage);
 
         // Asset 1 Returns
-        let strat_log_rets_1: Vec<f64> = self.construct_portfolio_returns(log_rets_1, &trading_costs, 1.0, self.weight_asset_1);
-        
+        let strat_log_rets_1: Vec<f64> = self.construct_portfolio_returns(&signals, log_rets_1, &trading_costs, 1.0, &weights_1);
+
         // Log Returns (including asset 2 returns assumed as pairs trade if provided)
         let log_returns: Vec<f64> = match log_rets_2_opt {
             Some(log_rets_2) => {
-                let strat_log_rets_2: Vec<f64> = self.construct_portfolio_returns(log_rets_2, &trading_costs, -1.0, self.weight_asset_2);
+                let strat_log_rets_2: Vec<f64> = self.construct_portfolio_returns(&signals, log_rets_2, &trading_costs, -1.0, &weights_2);
                 self.add_vecs(&strat_log_rets_1, &strat_log_rets_2)
             },
             None => strat_log_rets_1
@@ -147,13 +349,16 @@ impl Backtest {
         // Normalise returns
         let cum_norm_returns: Vec<f64> = normalise_returns(&strat_cum_log_rets);
 
-        // Win Rate Stats
-        let win_rate_stats: WinRate = self.win_rate_stats(&log_returns);
+        // Win Rate Stats and per-trade ledger
+        let (win_rate_stats, trade_records): (WinRate, Vec<TradeRecord>) = self.win_rate_stats(&signals, &log_returns, &cum_norm_returns);
 
         // Evaluation Metrics
-        let evaluation: Evaluation = Evaluation::new(log_returns, cum_norm_returns, win_rate_stats);
+        let evaluation: Evaluation = Evaluation::new(
+            log_returns, cum_norm_returns, win_rate_stats, trade_records,
+            self.periods_per_year, self.risk_free_rate
+        );
         let eval_metrics: Metrics = evaluation.run_evaluation_metrics();
-    
+
         // Return JSON string result
         Ok(eval_metrics)
     }
@@ -164,7 +369,7 @@ impl Backtest {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::Signals;
+    use crate::models::{Signals, SizingMode};
     use tradestats::metrics::{spread_standard, rolling_zscore};
     use csv::Reader;
     use serde::Deserialize;
@@ -231,11 +436,130 @@ mod tests {
         let net_signals: Vec<f64> = signals_obj.consolidate_signals(vec![long_signals, short_signals]);
         
         // Run Backtest
-        let backtest: Backtest = Backtest::new(net_signals, trading_costs, weighting_asset_1, weighting_asset_2);
+        let periods_per_year: f64 = 252.0; // daily returns
+        let risk_free_rate: f64 = 0.0;
+
+        let backtest: Backtest = Backtest::new(
+            net_signals, trading_costs, weighting_asset_1, weighting_asset_2,
+            None, None, None, periods_per_year, risk_free_rate, SizingMode::Fixed(1.0), 1.0
+        );
         let backtest_result: Result<Metrics, String> = backtest.run_backtest(log_rets_1, Some(log_rets_2));
         match backtest_result {
             Ok(_) => assert!(true),
             Err(_) => assert!(false)
         }
     }
+
+    #[test]
+    fn test_win_rate_stats_flushes_still_open_position() {
+        let backtest: Backtest = Backtest::new(
+            vec![0.0; 5], 0.0, 1.0, 1.0,
+            None, None, None, 252.0, 0.0, SizingMode::Fixed(1.0), 1.0
+        );
+
+        let signals: Vec<f64> = vec![0.0, 1.0, 1.0, 1.0, 1.0];
+        let log_rets: Vec<f64> = vec![0.0, 0.01, 0.01, 0.01, 0.01];
+        let cum_rets: Vec<f64> = cumulative_returns(&log_rets);
+
+        let (win_rate_stats, trade_records) = backtest.win_rate_stats(&signals, &log_rets, &cum_rets);
+
+        assert_eq!(win_rate_stats.opened, 1);
+        assert_eq!(win_rate_stats.closed, 0);
+        assert_eq!(trade_records.len(), 1);
+
+        let open_trade = &trade_records[0];
+        assert_eq!(open_trade.index_in, 1);
+        assert_eq!(open_trade.index_out, 4);
+        assert!(!open_trade.closed);
+    }
+
+    #[test]
+    fn test_win_rate_stats_resets_curr_profit_on_side_switch() {
+        let backtest: Backtest = Backtest::new(
+            vec![0.0; 6], 0.0, 1.0, 1.0,
+            None, None, None, 252.0, 0.0, SizingMode::Fixed(1.0), 1.0
+        );
+
+        // Long opens at 1, flips to short at 3 (no flat bar in between), short closes at 5
+        let signals: Vec<f64> = vec![0.0, 1.0, 1.0, -1.0, -1.0, 0.0];
+        let log_rets: Vec<f64> = vec![0.0, 0.01, 0.01, -0.01, -0.01, 0.0];
+        let cum_rets: Vec<f64> = cumulative_returns(&log_rets);
+
+        let (_, trade_records) = backtest.win_rate_stats(&signals, &log_rets, &cum_rets);
+
+        assert_eq!(trade_records.len(), 2);
+        assert_eq!(trade_records[0].log_pnl, 0.02);
+        // The short trade's PnL must not carry over leftover PnL from the closed long trade
+        assert_eq!(trade_records[1].log_pnl, -0.02);
+    }
+
+    #[test]
+    fn test_apply_exit_policy_forces_flat_on_stop_loss() {
+        // Position opens at index 1, then runs -0.01, -0.015 -> cumulative -0.025 crosses -0.02
+        let signals: Vec<f64> = vec![0.0, 1.0, 1.0, 1.0, 1.0];
+        let log_rets: Vec<f64> = vec![0.0, -0.01, -0.015, 0.05, 0.05];
+
+        let backtest: Backtest = Backtest::new(
+            signals.clone(), 0.0, 1.0, 1.0,
+            Some(0.02), None, None, 252.0, 0.0, SizingMode::Fixed(1.0), 1.0
+        );
+
+        let adjusted: Vec<f64> = backtest.apply_exit_policy(&log_rets);
+
+        assert_eq!(adjusted, vec![0.0, 1.0, 1.0, 0.0, 0.0]);
+        // original signal sequence is left untouched; only the adjusted copy reflects the forced exit
+        assert_eq!(backtest.signals, signals);
+    }
+
+    #[test]
+    fn test_apply_exit_policy_forces_flat_on_take_profit() {
+        // Position opens at index 1, then runs +0.01, +0.015 -> cumulative +0.025 crosses +0.02
+        let signals: Vec<f64> = vec![0.0, 1.0, 1.0, 1.0, 1.0];
+        let log_rets: Vec<f64> = vec![0.0, 0.01, 0.015, 0.05, 0.05];
+
+        let backtest: Backtest = Backtest::new(
+            signals, 0.0, 1.0, 1.0,
+            None, Some(0.02), None, 252.0, 0.0, SizingMode::Fixed(1.0), 1.0
+        );
+
+        let adjusted: Vec<f64> = backtest.apply_exit_policy(&log_rets);
+
+        assert_eq!(adjusted, vec![0.0, 1.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_leverage_series_vol_target_caps_at_max_leverage() {
+        // Tiny realized vol relative to the annual_vol target would otherwise call for a huge
+        // leverage multiple; max_leverage must still cap it
+        let window: usize = 3;
+        let sizing_mode = SizingMode::VolTarget { annual_vol: 10.0, window };
+        let max_leverage: f64 = 2.0;
+
+        let backtest: Backtest = Backtest::new(
+            vec![0.0; 6], 0.0, 1.0, 1.0,
+            None, None, None, 252.0, 0.0, sizing_mode, max_leverage
+        );
+
+        let strat_log_rets: Vec<f64> = vec![0.0001, -0.0001, 0.0001, -0.0001, 0.0001, -0.0001];
+        let leverage: Vec<f64> = backtest.leverage_series(&strat_log_rets);
+
+        // Defaults to 1.0 (capped) before the window fills
+        assert_eq!(leverage[..window], vec![1.0; window]);
+        // Capped at max_leverage once the window fills
+        for &lev in &leverage[window..] {
+            assert_eq!(lev, max_leverage);
+        }
+    }
+
+    #[test]
+    fn test_leverage_series_fixed_caps_at_max_leverage() {
+        let backtest: Backtest = Backtest::new(
+            vec![0.0; 4], 0.0, 1.0, 1.0,
+            None, None, None, 252.0, 0.0, SizingMode::Fixed(5.0), 2.0
+        );
+
+        let leverage: Vec<f64> = backtest.leverage_series(&vec![0.0; 4]);
+
+        assert_eq!(leverage, vec![2.0; 4]);
+    }
 }
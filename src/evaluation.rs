@@ -1,4 +1,4 @@
-use crate::models::WinRate;
+use crate::models::{TradeRecord, TradeStats, WinRate};
 use crate::utils::{normalise_returns, round_float};
 use serde::{Deserialize, Serialize};
 
@@ -11,8 +11,11 @@ pub struct Metrics {
   pub mean_return: f64,
   pub sharpe_ratio: f64,
   pub sortino_ratio: f64,
+  pub calmar_ratio: f64,
   pub total_return: f64,
-  pub win_rate_stats: WinRate
+  pub win_rate_stats: WinRate,
+  pub trade_records: Vec<TradeRecord>,
+  pub trade_stats: TradeStats
 }
 
 #[derive(Debug)]
@@ -20,22 +23,40 @@ pub struct Evaluation {
   pub log_returns: Vec<f64>,
   pub cum_norm_returns: Vec<f64>,
   pub win_rate_stats: WinRate,
+  pub trade_records: Vec<TradeRecord>,
+  pub periods_per_year: f64, // e.g. 252 for daily, 52 for weekly, 12 for monthly returns
+  pub risk_free_rate: f64, // annual risk-free rate, de-annualized internally per period
 }
 
 impl Evaluation {
-  pub fn new(log_returns: Vec<f64>, cum_norm_returns: Vec<f64>, win_rate_stats: WinRate) -> Self {
+  pub fn new(
+    log_returns: Vec<f64>,
+    cum_norm_returns: Vec<f64>,
+    win_rate_stats: WinRate,
+    trade_records: Vec<TradeRecord>,
+    periods_per_year: f64,
+    risk_free_rate: f64
+  ) -> Self {
     Self {
       log_returns,
       cum_norm_returns,
       win_rate_stats,
+      trade_records,
+      periods_per_year,
+      risk_free_rate,
     }
   }
 
+  /// Risk-Free Rate Per Period
+  /// De-annualizes risk_free_rate to match the periodicity of log_returns
+  fn rf_period(&self) -> f64 {
+    (1.0 + self.risk_free_rate).powf(1.0 / self.periods_per_year) - 1.0
+  }
+
   // Annual Rate of Return
   fn annual_rate_of_return(&self) -> f64 {
     let mean_return: f64 = self.mean_return();
-    let periods_per_year: f64 = 252.0; // for daily returns
-    (1.0 + mean_return).powf(periods_per_year) - 1.0
+    (1.0 + mean_return).powf(self.periods_per_year) - 1.0
   }
 
   /// Drawdowns
@@ -69,29 +90,37 @@ impl Evaluation {
   }
 
   /// Sharpe Ratio
+  /// Annualized, risk-free-adjusted: mean excess return over its std dev, scaled by sqrt(periods_per_year)
   fn sharpe_ratio(&self) -> f64 {
     let n: f64 = self.log_returns.len() as f64;
     if n == 0.0 { return 0.0; };
 
-    let mean: f64 = self.log_returns.iter().sum::<f64>() / n;
+    let rf_period: f64 = self.rf_period();
+    let excess_returns: Vec<f64> = self.log_returns.iter().map(|&x| x - rf_period).collect();
+
+    let mean: f64 = excess_returns.iter().sum::<f64>() / n;
     if mean == 0.0 { return 0.0; };
 
-    let variance: f64 = self.log_returns.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / n;
+    let variance: f64 = excess_returns.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / n;
     if variance == 0.0 { return 0.0; };
 
-    mean / variance.sqrt()
+    (mean / variance.sqrt()) * self.periods_per_year.sqrt()
   }
 
-  /// Sortino Ratio without risk-free rate
+  /// Sortino Ratio
+  /// Annualized, risk-free-adjusted: mean excess return over downside deviation, scaled by sqrt(periods_per_year)
   fn sortino_ratio(&self) -> f64 {
     let n: f64 = self.log_returns.len() as f64;
     if n == 0.0 { return 0.0; };
 
-    let mean: f64 = self.log_returns.iter().sum::<f64>() / n;
+    let rf_period: f64 = self.rf_period();
+    let excess_returns: Vec<f64> = self.log_returns.iter().map(|&x| x - rf_period).collect();
+
+    let mean: f64 = excess_returns.iter().sum::<f64>() / n;
     if mean == 0.0 { return 0.0; };
 
-    // Filter only negative returns
-    let negative_returns: Vec<f64> = self.log_returns.iter().filter(|&&x| x < 0.0).map(|&x| x.powi(2)).collect();
+    // Filter only negative excess returns
+    let negative_returns: Vec<f64> = excess_returns.iter().filter(|&&x| x < 0.0).map(|&x| x.powi(2)).collect();
     let n_neg: f64 = negative_returns.len() as f64;
 
     if n_neg == 0.0 { return 0.0; };
@@ -99,7 +128,17 @@ impl Evaluation {
     let downside_variance: f64 = negative_returns.iter().sum::<f64>() / n_neg;
     if downside_variance == 0.0 { return 0.0; };
 
-    mean / downside_variance.sqrt()
+    (mean / downside_variance.sqrt()) * self.periods_per_year.sqrt()
+  }
+
+  /// Calmar Ratio
+  /// Annualized return relative to max drawdown, computed from full-precision values so small
+  /// drawdowns aren't rounded away to zero before dividing
+  fn calmar_ratio(&self) -> f64 {
+    let arr: f64 = self.annual_rate_of_return();
+    let max_drawdown: f64 = self.drawdowns().iter().cloned().fold(f64::NAN, f64::min);
+    if max_drawdown == 0.0 { return 0.0; };
+    arr / max_drawdown.abs()
   }
 
   /// Total Return
@@ -107,6 +146,81 @@ impl Evaluation {
     self.cum_norm_returns[self.cum_norm_returns.len() - 1]
   }
 
+  /// Consecutive Runs
+  /// Finds the longest runs of consecutive winning and losing trades in a PnL series
+  fn consecutive_runs(&self, pnls: &Vec<f64>) -> (u32, u32) {
+    let mut max_wins: u32 = 0;
+    let mut max_losses: u32 = 0;
+    let mut curr_wins: u32 = 0;
+    let mut curr_losses: u32 = 0;
+
+    for &pnl in pnls {
+      if pnl > 0.0 {
+        curr_wins += 1;
+        curr_losses = 0;
+      } else if pnl < 0.0 {
+        curr_losses += 1;
+        curr_wins = 0;
+      } else {
+        curr_wins = 0;
+        curr_losses = 0;
+      }
+      max_wins = max_wins.max(curr_wins);
+      max_losses = max_losses.max(curr_losses);
+    }
+
+    (max_wins, max_losses)
+  }
+
+  /// Trade Stats
+  /// Profit factor, average win/loss, payoff ratio, expectancy and consecutive win/loss streaks
+  fn trade_stats(&self) -> TradeStats {
+    // Excludes any still-open trade (closed: false) so unrealized PnL doesn't bleed into
+    // headline figures computed over realized trades
+    let pnls: Vec<f64> = self.trade_records.iter().filter(|t| t.closed).map(|t| t.log_pnl).collect();
+    let wins: Vec<f64> = pnls.iter().cloned().filter(|&p| p > 0.0).collect();
+    let losses: Vec<f64> = pnls.iter().cloned().filter(|&p| p < 0.0).collect();
+
+    let gross_profit: f64 = wins.iter().sum();
+    let gross_loss: f64 = losses.iter().sum::<f64>().abs();
+
+    let profit_factor: f64 = match (gross_profit, gross_loss) {
+      (0.0, 0.0) => 0.0,
+      (_, 0.0) => f64::INFINITY,
+      _ => gross_profit / gross_loss,
+    };
+
+    let avg_win: f64 = if wins.is_empty() { 0.0 } else { gross_profit / wins.len() as f64 };
+    let avg_loss: f64 = if losses.is_empty() { 0.0 } else { losses.iter().sum::<f64>() / losses.len() as f64 };
+
+    let payoff_ratio: f64 = match (avg_win, avg_loss) {
+      (0.0, 0.0) => 0.0,
+      (_, 0.0) => f64::INFINITY,
+      _ => avg_win / avg_loss.abs(),
+    };
+
+    // Recomputed from the exact closed/closed_profit counts rather than the display-rounded
+    // WinRate.win_rate field, so expectancy doesn't bake in avoidable rounding error
+    let win_rate: f64 = if self.win_rate_stats.closed > 0 {
+      self.win_rate_stats.closed_profit as f64 / self.win_rate_stats.closed as f64
+    } else {
+      0.0
+    };
+    let expectancy: f64 = win_rate * avg_win + (1.0 - win_rate) * avg_loss;
+
+    let (max_consecutive_wins, max_consecutive_losses) = self.consecutive_runs(&pnls);
+
+    TradeStats {
+      profit_factor: round_float(profit_factor, 2),
+      avg_win: round_float(avg_win, 4),
+      avg_loss: round_float(avg_loss, 4),
+      payoff_ratio: round_float(payoff_ratio, 2),
+      expectancy: round_float(expectancy, 4),
+      max_consecutive_wins,
+      max_consecutive_losses
+    }
+  }
+
   /// Run Evaluation Metrics
   /// Calculates metrics and returns net evaluation serialized
   pub fn run_evaluation_metrics(&self) -> Metrics {
@@ -118,11 +232,122 @@ impl Evaluation {
     let mean_return: f64 = round_float(self.mean_return(), 3);
     let sharpe_ratio: f64 = round_float(self.sharpe_ratio(), 2);
     let sortino_ratio: f64 = round_float(self.sortino_ratio(), 2);
+    let calmar_ratio: f64 = round_float(self.calmar_ratio(), 2);
     let total_return: f64 = round_float(self.total_return(), 2);
     let win_rate_stats: WinRate = self.win_rate_stats.to_owned();
+    let trade_records: Vec<TradeRecord> = self.trade_records.to_owned();
+    let trade_stats: TradeStats = self.trade_stats();
+
+    Metrics { arr, drawdowns, equity_curve, max_drawdown, mean_return,
+      sharpe_ratio, sortino_ratio, calmar_ratio, total_return, win_rate_stats, trade_records, trade_stats }
+  }
+
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::backtest::Backtest;
+  use crate::models::SizingMode;
+  use crate::utils::cumulative_returns;
+
+  fn trade(log_pnl: f64, closed: bool) -> TradeRecord {
+    TradeRecord {
+      index_in: 0, index_out: 1, direction: 1.0,
+      cum_return_in: 0.0, cum_return_out: 0.0,
+      log_pnl, bars_held: 1, closed
+    }
+  }
+
+  fn evaluation(trade_records: Vec<TradeRecord>, closed: u32, closed_profit: u32) -> Evaluation {
+    let win_rate_stats = WinRate { win_rate: 0.0, opened: closed, closed, closed_profit };
+    Evaluation::new(vec![0.0], vec![0.0], win_rate_stats, trade_records, 252.0, 0.0)
+  }
+
+  #[test]
+  fn test_trade_stats_profit_factor_infinity_when_no_losers() {
+    let trade_records = vec![trade(1.0, true), trade(2.0, true)];
+    let evaluation = evaluation(trade_records, 2, 2);
+    let trade_stats = evaluation.trade_stats();
 
-    Metrics { arr, drawdowns, equity_curve, max_drawdown, mean_return, 
-      sharpe_ratio, sortino_ratio, total_return, win_rate_stats }
+    assert_eq!(trade_stats.profit_factor, f64::INFINITY);
+    assert_eq!(trade_stats.payoff_ratio, f64::INFINITY);
   }
 
+  #[test]
+  fn test_trade_stats_all_zero_when_no_trades() {
+    let evaluation = evaluation(vec![], 0, 0);
+    let trade_stats = evaluation.trade_stats();
+
+    assert_eq!(trade_stats.profit_factor, 0.0);
+    assert_eq!(trade_stats.payoff_ratio, 0.0);
+    assert_eq!(trade_stats.expectancy, 0.0);
+    assert_eq!(trade_stats.max_consecutive_wins, 0);
+    assert_eq!(trade_stats.max_consecutive_losses, 0);
+  }
+
+  #[test]
+  fn test_trade_stats_excludes_still_open_trade() {
+    let trade_records = vec![trade(1.0, true), trade(100.0, false)];
+    let evaluation = evaluation(trade_records, 1, 1);
+    let trade_stats = evaluation.trade_stats();
+
+    assert_eq!(trade_stats.avg_win, 1.0);
+    assert_eq!(trade_stats.profit_factor, f64::INFINITY);
+  }
+
+  #[test]
+  fn test_trade_stats_from_side_switch_scan_does_not_bleed_pnl_across_trades() {
+    let backtest: Backtest = Backtest::new(
+      vec![0.0; 6], 0.0, 1.0, 1.0,
+      None, None, None, 252.0, 0.0, SizingMode::Fixed(1.0), 1.0
+    );
+
+    // Long opens at 1, flips to short at 3 (no flat bar in between), short closes at 5
+    let signals: Vec<f64> = vec![0.0, 1.0, 1.0, -1.0, -1.0, 0.0];
+    let log_rets: Vec<f64> = vec![0.0, 0.01, 0.01, -0.01, -0.01, 0.0];
+    let cum_rets: Vec<f64> = cumulative_returns(&log_rets);
+
+    let (win_rate_stats, trade_records) = backtest.win_rate_stats(&signals, &log_rets, &cum_rets);
+    let evaluation = Evaluation::new(vec![0.0], vec![0.0], win_rate_stats, trade_records, 252.0, 0.0);
+    let trade_stats = evaluation.trade_stats();
+
+    // One winning trade (+0.02) and one losing trade (-0.02), not a bled-together 0.0
+    assert_eq!(trade_stats.avg_win, 0.02);
+    assert_eq!(trade_stats.avg_loss, -0.02);
+    assert_eq!(trade_stats.profit_factor, 1.0);
+  }
+
+  fn evaluation_with_returns(log_returns: Vec<f64>, periods_per_year: f64, risk_free_rate: f64) -> Evaluation {
+    let win_rate_stats = WinRate { win_rate: 0.0, opened: 0, closed: 0, closed_profit: 0 };
+    Evaluation::new(log_returns, vec![0.0], win_rate_stats, vec![], periods_per_year, risk_free_rate)
+  }
+
+  #[test]
+  fn test_sharpe_ratio_applies_risk_free_rate_and_annualizes() {
+    // risk_free_rate de-annualizes to exactly 0.01 per period (1.01^4 = 1.04060401)
+    let evaluation = evaluation_with_returns(vec![0.01, 0.03, 0.01, 0.03], 4.0, 0.04060401);
+    let sharpe_ratio: f64 = evaluation.sharpe_ratio();
+
+    // Excess returns become [0.0, 0.02, 0.0, 0.02]: mean 0.01, std 0.01, scaled by sqrt(4) = 2
+    assert!((sharpe_ratio - 2.0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn test_sortino_ratio_uses_downside_deviation_only() {
+    let evaluation = evaluation_with_returns(vec![-0.01, 0.03, -0.01, 0.03], 4.0, 0.0);
+    let sortino_ratio: f64 = evaluation.sortino_ratio();
+
+    // Mean 0.01, downside deviation from the two -0.01 bars is 0.01, scaled by sqrt(4) = 2
+    assert!((sortino_ratio - 2.0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn test_calmar_ratio_against_hand_computed_drawdown() {
+    let evaluation = evaluation_with_returns(vec![0.01, 0.02, -0.01, 0.02], 1.0, 0.0);
+    let calmar_ratio: f64 = evaluation.calmar_ratio();
+
+    // arr = exp(0.01) - 1, max_drawdown = exp(0.02) - exp(-0.01)
+    assert!((calmar_ratio - 0.3333222224999909).abs() < 1e-9);
+  }
 }
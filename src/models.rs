@@ -116,6 +116,17 @@ impl Signals {
   }
 }
 
+/*
+  Sizing Mode
+  Alternative ways to scale the capital weighting fed into Backtest::construct_portfolio_returns
+*/
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum SizingMode {
+  Fixed(f64),
+  VolTarget { annual_vol: f64, window: usize }
+}
+
 /*
   Win Rate Stats
   Figures for number of trades placed
@@ -128,3 +139,36 @@ pub struct WinRate {
   pub closed: u32,
   pub closed_profit: u32
 }
+
+/*
+  Trade Record
+  Individual entry/exit ledger row for a trade (a position still open at the end of the series is included with closed: false)
+*/
+
+#[derive(Debug, Deserialize, Serialize, Clone, TS)]
+pub struct TradeRecord {
+  pub index_in: usize,
+  pub index_out: usize,
+  pub direction: f64, // +1.0 long, -1.0 short
+  pub cum_return_in: f64,
+  pub cum_return_out: f64,
+  pub log_pnl: f64, // net of trading_costs
+  pub bars_held: usize,
+  pub closed: bool // false when the series ends mid-trade: index_out/log_pnl mark the last bar scanned, not an exit
+}
+
+/*
+  Trade Stats
+  Headline trade-performance figures derived from the per-trade PnL series
+*/
+
+#[derive(Debug, Deserialize, Serialize, Clone, TS)]
+pub struct TradeStats {
+  pub profit_factor: f64, // f64::INFINITY when there are winning but no losing trades, 0.0 when there are neither
+  pub avg_win: f64,
+  pub avg_loss: f64,
+  pub payoff_ratio: f64, // f64::INFINITY when there are winning but no losing trades, 0.0 when there are neither
+  pub expectancy: f64,
+  pub max_consecutive_wins: u32,
+  pub max_consecutive_losses: u32
+}
@@ -21,3 +21,59 @@ pub fn cumulative_returns(log_returns: &Vec<f64>) -> Vec<f64> {
     Some(*state)
   }).collect()
 }
+
+/// Rolling Hedge Ratio
+/// Computes a trailing OLS beta of series_1 on series_2 over `window` observations, for use
+/// as a time-varying hedge ratio in place of a fixed pairs weighting. Before the window fills,
+/// beta defaults to 1.0.
+pub fn rolling_hedge_ratio(series_1: &Vec<f64>, series_2: &Vec<f64>, window: usize) -> Vec<f64> {
+  let n: usize = series_1.len();
+  let mut beta: Vec<f64> = vec![1.0; n];
+
+  for i in window..n {
+    let window_1: &[f64] = &series_1[i - window..i];
+    let window_2: &[f64] = &series_2[i - window..i];
+
+    let mean_1: f64 = window_1.iter().sum::<f64>() / window as f64;
+    let mean_2: f64 = window_2.iter().sum::<f64>() / window as f64;
+
+    let cov: f64 = window_1.iter().zip(window_2.iter())
+      .map(|(&a, &b)| (a - mean_1) * (b - mean_2))
+      .sum::<f64>() / window as f64;
+    let var_2: f64 = window_2.iter().map(|&b| (b - mean_2).powi(2)).sum::<f64>() / window as f64;
+
+    beta[i] = if var_2 == 0.0 { 1.0 } else { cov / var_2 };
+  }
+
+  beta
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rolling_hedge_ratio_computes_ols_beta_once_window_fills() {
+    let series_2: Vec<f64> = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+    let series_1: Vec<f64> = series_2.iter().map(|&x| 2.0 * x).collect();
+
+    let beta: Vec<f64> = rolling_hedge_ratio(&series_1, &series_2, 3);
+
+    // Defaults to 1.0 before the window fills
+    assert_eq!(&beta[..3], &[1.0, 1.0, 1.0]);
+    // series_1 is exactly 2x series_2, so the OLS beta is 2.0 once the window fills
+    for &b in &beta[3..] {
+      assert!((b - 2.0).abs() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn test_rolling_hedge_ratio_defaults_to_one_when_window_variance_is_zero() {
+    let series_1: Vec<f64> = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+    let series_2: Vec<f64> = vec![5.0; 6];
+
+    let beta: Vec<f64> = rolling_hedge_ratio(&series_1, &series_2, 3);
+
+    assert_eq!(beta, vec![1.0; 6]);
+  }
+}